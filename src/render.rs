@@ -21,9 +21,11 @@ use bevy::{
     math::{FloatOrd, IVec2, Rect, Vec2, Vec3, Vec4},
 };
 use cosmic_text::{
-    ttf_parser::{Face, GlyphId},
+    ttf_parser::{Face, GlyphId, Tag},
     Attrs, Buffer, Family, FontSystem, LayoutGlyph, Metrics, Shaping, Weight, Wrap,
 };
+use rayon::prelude::*;
+use std::collections::HashMap;
 use std::num::NonZero;
 
 fn default_mesh() -> Mesh {
@@ -76,6 +78,7 @@ pub fn text_render(
     segments: Query<Ref<FetchedTextSegment>>,
     mut draw_requests: Local<Vec<DrawRequest>>,
     mut sort_buffer: Local<Vec<(Layer, [u16; 6])>>,
+    mut frame: Local<u64>,
 ) {
     let Ok(mut lock) = font_system.0.try_lock() else {
         return;
@@ -93,6 +96,74 @@ pub fn text_render(
     }
     let font_system = &mut lock.font_system;
     let scale_factor = settings.scale_factor;
+    let border = GlyphBorder {
+        padding: settings.glyph_padding,
+        margin: settings.glyph_margin,
+    };
+    // Monotonic tick used as the recency clock for atlas LRU eviction.
+    *frame = frame.wrapping_add(1);
+    let frame = *frame;
+
+    // Cross-entity parallel pre-pass: gather the new glyphs of *every* text
+    // entity into one batch, rasterize the batch on the rayon pool, then pack
+    // the results into each entity's atlas serially. Batching across entities
+    // keeps a frame that introduces many glyphs across many entities from
+    // stalling the schedule thread entity-by-entity.
+    {
+        let mut misses: HashMap<(AssetId<TextAtlas>, GlyphEntry), GlyphMiss> = HashMap::new();
+        let mut working_set: Vec<(AssetId<TextAtlas>, GlyphEntry)> = Vec::new();
+        for (text, bounds, styling, atlas_handle, ..) in text_query.iter() {
+            let atlas_id = atlas_handle.0.id();
+            // Ensure the atlas has a backing image so the pack step can write it.
+            {
+                let Some(atlas) = atlases.get_mut(atlas_id) else {
+                    continue;
+                };
+                if atlas.image.id() == AssetId::default() || !images.contains(atlas.image.id()) {
+                    atlas.image = images.add(TextAtlas::empty_image(
+                        settings.default_atlas_dimension.0,
+                        settings.default_atlas_dimension.1,
+                    ));
+                }
+            }
+            let buffer = shape_buffer(font_system, &styling, &text, &bounds, &segments);
+            let Some(atlas) = atlases.get(atlas_id) else {
+                continue;
+            };
+            collect_misses(
+                &styling,
+                &text,
+                &buffer,
+                atlas,
+                atlas_id,
+                &mut misses,
+                &mut working_set,
+            );
+        }
+        // Pre-touch this frame's working set so alpha glyphs still in use are
+        // never eviction candidates when the misses below are packed under
+        // capacity pressure (color entries are not in the working set and are
+        // not pre-touched) — otherwise a glyph last touched on an earlier frame carries
+        // stale recency through `pack_raster` and can be evicted, only to be
+        // re-cached immediately on the `get_atlas_rect` miss (atlas thrash).
+        for (atlas_id, entry) in &working_set {
+            if let Some(atlas) = atlases.get_mut(*atlas_id) {
+                if atlas.glyphs.contains_key(entry) {
+                    atlas.touch(entry, frame);
+                }
+            }
+        }
+        rasterize_and_pack(
+            font_system,
+            &mut atlases,
+            &mut images,
+            misses,
+            scale_factor,
+            border,
+            frame,
+        );
+    }
+
     for (text, bounds, styling, atlas, mut mesh2d, mut mesh3d, mut output) in text_query.iter_mut()
     {
         let Some(atlas) = atlases.get_mut(atlas.0.id()) else {
@@ -148,40 +219,11 @@ pub fn text_render(
             }
         }
 
-        let mut buffer = Buffer::new(
-            font_system,
-            Metrics::new(styling.size, styling.size * styling.line_height),
-        );
-        buffer.set_wrap(font_system, Wrap::WordOrGlyph);
-        buffer.set_size(font_system, Some(bounds.width), None);
-        buffer.set_tab_width(font_system, styling.tab_width);
-
-        buffer.set_rich_text(
-            font_system,
-            text.segments
-                .iter()
-                .enumerate()
-                .map(|(idx, (text, style))| {
-                    (
-                        match text {
-                            Text3dSegment::String(s) => s.as_str(),
-                            Text3dSegment::Extract(e) => segments
-                                .get(*e)
-                                .map(|x| x.into_inner().as_str())
-                                .unwrap_or(""),
-                        },
-                        style.as_attr(&styling).metadata(idx),
-                    )
-                }),
-            &Attrs::new()
-                .family(Family::Name(&styling.font))
-                .style(styling.style.into())
-                .weight(styling.weight.into()),
-            Shaping::Advanced,
-            None,
-        );
-
-        buffer.shape_until_scroll(font_system, true);
+        // Shaping matches the cross-entity pre-pass above, so the glyphs this
+        // loop references were already tessellated and packed in parallel; the
+        // `get_atlas_rect` calls below are cache hits save for color/bitmap
+        // glyphs the pre-pass deliberately defers to the inline color path.
+        let buffer = shape_buffer(font_system, &styling, &text, &bounds, &segments);
 
         let Some(mesh) = get_mesh(&mut mesh2d, &mut mesh3d, &mut meshes) else {
             continue;
@@ -224,7 +266,7 @@ pub fn text_render(
                 {
                     match request {
                         DrawType::Glyph(stroke) => {
-                            let Some((pixel_rect, base)) = get_atlas_rect(
+                            let Some((pixel_rect, base, color_glyph)) = get_atlas_rect(
                                 font_system,
                                 scale_factor,
                                 &styling,
@@ -234,6 +276,9 @@ pub fn text_render(
                                 glyph,
                                 attrs,
                                 stroke,
+                                border,
+                                frame,
+                                color,
                             ) else {
                                 continue;
                             };
@@ -252,6 +297,7 @@ pub fn text_render(
                                 base,
                                 pixel_rect,
                                 color,
+                                color_glyph,
                                 scale_factor,
                                 layer,
                                 real_index,
@@ -359,6 +405,208 @@ pub fn text_render(
     }
 }
 
+/// Per-glyph border reserved in the atlas, in atlas texels.
+///
+/// `padding` is empty space kept *inside* the sampled UV rect, and `margin` is
+/// space reserved *between* packed glyphs but left *outside* the UV rect, so
+/// that bilinear sampling at a glyph's edge only ever blends in transparent
+/// texels. The packer reserves `glyph_size + 2 * (padding + margin)` and the UV
+/// rect covers the padded-but-not-margined inner box.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlyphBorder {
+    pub padding: u32,
+    pub margin: u32,
+}
+
+/// A coverage raster produced off-thread, ready to be packed into the atlas.
+/// This is the hand-off between the parallel rasterization stage and the serial
+/// packing stage: it owns its pixels and carries the placement offset the atlas
+/// records alongside the packed rectangle.
+pub(crate) struct GlyphRaster {
+    pub coverage: Vec<u8>,
+    pub size: IVec2,
+    pub offset: Vec2,
+}
+
+/// Inputs describing a single uncached glyph: everything a worker needs to
+/// rasterize the outline without touching shared atlas state.
+struct GlyphMiss {
+    entry: GlyphEntry,
+    font: cosmic_text::fontdb::ID,
+    glyph_id: u16,
+    font_size: f32,
+    stroke: Option<NonZero<u32>>,
+    join: StrokeJoin,
+    variations: Vec<(Tag, f32)>,
+    slant: f32,
+}
+
+/// Build and shape a cosmic-text buffer for one text entity. Shared by the
+/// parallel pre-pass and the per-entity mesh build so both observe identical
+/// layout runs.
+fn shape_buffer(
+    font_system: &mut FontSystem,
+    styling: &Text3dStyling,
+    text: &Text3d,
+    bounds: &Text3dBounds,
+    segments: &Query<Ref<FetchedTextSegment>>,
+) -> Buffer {
+    let mut buffer = Buffer::new(
+        font_system,
+        Metrics::new(styling.size, styling.size * styling.line_height),
+    );
+    buffer.set_wrap(font_system, Wrap::WordOrGlyph);
+    buffer.set_size(font_system, Some(bounds.width), None);
+    buffer.set_tab_width(font_system, styling.tab_width);
+    buffer.set_rich_text(
+        font_system,
+        text.segments
+            .iter()
+            .enumerate()
+            .map(|(idx, (text, style))| {
+                (
+                    match text {
+                        Text3dSegment::String(s) => s.as_str(),
+                        Text3dSegment::Extract(e) => segments
+                            .get(*e)
+                            .map(|x| x.into_inner().as_str())
+                            .unwrap_or(""),
+                    },
+                    style.as_attr(styling).metadata(idx),
+                )
+            }),
+        &Attrs::new()
+            .family(Family::Name(&styling.font))
+            .style(styling.style.into())
+            .weight(styling.weight.into()),
+        Shaping::Advanced,
+        None,
+    );
+    buffer.shape_until_scroll(font_system, true);
+    buffer
+}
+
+/// Walk one shaped buffer and record, against `atlas_id`, the alpha-coverage
+/// glyph entries it references (`working_set`) plus the ones not yet cached
+/// (`misses`). Color entries, keyed on the draw-time fill, are not recorded.
+/// Collecting across every entity before rasterizing lets a frame's
+/// new glyphs fan out in a single parallel batch rather than entity-by-entity.
+fn collect_misses(
+    styling: &Text3dStyling,
+    text: &Text3d,
+    buffer: &Buffer,
+    atlas: &TextAtlas,
+    atlas_id: AssetId<TextAtlas>,
+    misses: &mut HashMap<(AssetId<TextAtlas>, GlyphEntry), GlyphMiss>,
+    working_set: &mut Vec<(AssetId<TextAtlas>, GlyphEntry)>,
+) {
+    for run in buffer.layout_runs() {
+        for glyph in run.glyphs {
+            let Some((_, attrs)) = text.segments.get(glyph.metadata) else {
+                continue;
+            };
+            let variations = resolve_variations(styling, attrs);
+            let variation_key = variation_key(&variations);
+            let slant = styling.synthetic_oblique(attrs);
+            for stroke in styling.glyph_strokes(attrs) {
+                let entry = GlyphEntry {
+                    font: glyph.font_id,
+                    glyph_id: glyph.glyph_id.into(),
+                    size: FloatOrd(glyph.font_size),
+                    weight: styling.weight,
+                    join: styling.stroke_join,
+                    stroke,
+                    color: false,
+                    variations: variation_key.clone(),
+                    slant: FloatOrd(slant),
+                    fill: None,
+                };
+                working_set.push((atlas_id, entry.clone()));
+                let map_key = (atlas_id, entry.clone());
+                if atlas.glyphs.contains_key(&entry) || misses.contains_key(&map_key) {
+                    continue;
+                }
+                misses.insert(
+                    map_key,
+                    GlyphMiss {
+                        entry,
+                        font: glyph.font_id,
+                        glyph_id: glyph.glyph_id,
+                        font_size: glyph.font_size,
+                        stroke,
+                        join: styling.stroke_join,
+                        variations: variations.clone(),
+                        slant,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Rasterize every collected miss on the rayon pool, then pack the coverage
+/// rasters into their target atlases serially (packing mutates shared atlas
+/// state and grows the backing image, so it must stay single-threaded).
+fn rasterize_and_pack(
+    font_system: &FontSystem,
+    atlases: &mut Assets<TextAtlas>,
+    images: &mut Assets<Image>,
+    misses: HashMap<(AssetId<TextAtlas>, GlyphEntry), GlyphMiss>,
+    scale_factor: f32,
+    border: GlyphBorder,
+    frame: u64,
+) {
+    if misses.is_empty() {
+        return;
+    }
+    let db = font_system.db();
+    let rasters: Vec<(AssetId<TextAtlas>, GlyphEntry, GlyphRaster)> = misses
+        .into_iter()
+        .map(|((atlas_id, _), miss)| (atlas_id, miss))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .filter_map(|(atlas_id, miss)| {
+            db.with_face_data(miss.font, |file, _| {
+                let mut face = Face::parse(file, 0).ok()?;
+                for &(tag, value) in &miss.variations {
+                    face.set_variation(tag, value);
+                }
+                let glyph_id = GlyphId(miss.glyph_id);
+                // Color glyphs need per-layer / bitmap compositing that the
+                // monochrome outline path can't reproduce; skip them here so they
+                // aren't cached as an alpha blob, and let the inline color path in
+                // `cache_glyph` handle them. Stroked requests always outline.
+                if miss.stroke.is_none()
+                    && (face.glyph_raster_image(glyph_id, u16::MAX).is_some()
+                        || face.glyph_color_layers(glyph_id).is_some())
+                {
+                    return None;
+                }
+                let unit_per_em = face.units_per_em() as f32;
+                let mut commands = CommandEncoder::default();
+                commands.shear = if face.is_italic() { 0.0 } else { miss.slant };
+                face.outline_glyph(glyph_id, &mut commands)?;
+                let stroke = miss.stroke.map(|x| x.get() as f32 * unit_per_em / 100.);
+                let scale = miss.font_size / unit_per_em * scale_factor;
+                commands
+                    .rasterize_glyph(stroke, miss.join, scale)
+                    .map(|raster| (atlas_id, miss.entry, raster))
+            })
+            .flatten()
+        })
+        .collect();
+
+    for (atlas_id, entry, raster) in rasters {
+        let Some(atlas) = atlases.get_mut(atlas_id) else {
+            continue;
+        };
+        let Some(image) = images.get_mut(atlas.image.id()) else {
+            continue;
+        };
+        atlas.pack_raster(image, entry, raster, border, frame);
+    }
+}
+
 fn get_atlas_rect(
     font_system: &mut FontSystem,
     scale_factor: f32,
@@ -369,18 +617,43 @@ fn get_atlas_rect(
     glyph: &LayoutGlyph,
     attrs: &SegmentStyle,
     stroke: Option<NonZero<u32>>,
-) -> Option<(Rect, Vec2)> {
-    atlas
-        .glyphs
-        .get(&GlyphEntry {
-            font: glyph.font_id,
-            glyph_id: glyph.glyph_id.into(),
-            size: FloatOrd(glyph.font_size),
-            weight: styling.weight,
-            join: styling.stroke_join,
-            stroke,
-        })
-        .copied()
+    border: GlyphBorder,
+    frame: u64,
+    fill: Vec4,
+) -> Option<(Rect, Vec2, bool)> {
+    // Segment axis settings override the styling defaults; the resolved list is
+    // part of the cache key so differently-configured instances cache apart.
+    let variations = resolve_variations(styling, attrs);
+    let variation_key = variation_key(&variations);
+    // Requested faux-italic shear; keyed so sheared and upright variants cache
+    // apart. `cache_glyph` ignores it for faces that already have a true slant.
+    let slant = styling.synthetic_oblique(attrs);
+    let key = |color| GlyphEntry {
+        font: glyph.font_id,
+        glyph_id: glyph.glyph_id.into(),
+        size: FloatOrd(glyph.font_size),
+        weight: styling.weight,
+        join: styling.stroke_join,
+        stroke,
+        color,
+        variations: variation_key.clone(),
+        slant: FloatOrd(if color { 0.0 } else { slant }),
+        // Color entries bake the fill; alpha entries are tinted at draw time.
+        fill: if color { Some(quantize_fill(fill)) } else { None },
+    };
+    // A glyph is cached as either an alpha-coverage or a full-color entry, never
+    // both; probe the alpha key first since the common case is plain text.
+    let hit = |atlas: &mut TextAtlas, color| {
+        let entry = key(color);
+        let found = atlas.glyphs.get(&entry).copied();
+        if found.is_some() {
+            // Bump recency so the LRU policy keeps live glyphs resident.
+            atlas.touch(&entry, frame);
+        }
+        found.map(|(rect, offset)| (rect, offset, color))
+    };
+    hit(atlas, false)
+        .or_else(|| hit(atlas, true))
         .or_else(|| {
             font_system
                 .db()
@@ -397,12 +670,17 @@ fn get_atlas_rect(
                         stroke,
                         styling.stroke_join,
                         attrs.weight.unwrap_or(styling.weight).into(),
+                        &variations,
+                        border,
+                        frame,
+                        slant,
+                        fill,
                         face,
                     )
                 })
                 .flatten()
         })
-        .map(|(rect, offset)| (rect, offset / scale_factor))
+        .map(|(rect, offset, color)| (rect, offset / scale_factor, color))
 }
 
 pub(crate) fn cache_glyph(
@@ -414,9 +692,48 @@ pub(crate) fn cache_glyph(
     stroke: Option<NonZero<u32>>,
     stroke_join: StrokeJoin,
     weight: Weight,
-    face: Face,
-) -> Option<(Rect, Vec2)> {
+    variations: &[(Tag, f32)],
+    border: GlyphBorder,
+    frame: u64,
+    slant: f32,
+    fill: Vec4,
+    mut face: Face,
+) -> Option<(Rect, Vec2, bool)> {
+    for &(tag, value) in variations {
+        face.set_variation(tag, value);
+    }
     let unit_per_em = face.units_per_em() as f32;
+    let glyph_id = GlyphId(glyph.glyph_id);
+    let variation_key = variation_key(variations);
+    // Key on the *requested* slant to match `get_atlas_rect` and the prepass;
+    // only the applied shear is suppressed for faces that already slant, so a
+    // true-italic glyph still resolves to the same cache entry every frame.
+    let applied_slant = if face.is_italic() { 0.0 } else { slant };
+    let scale = glyph.font_size / unit_per_em * scale_factor;
+    // For unstroked requests, color data takes precedence over the monochrome
+    // outline: a COLR/CPAL glyph composites tinted layers and an embedded
+    // bitmap is blitted as-is, so the per-segment fill tint is not applied to
+    // them. Stroked requests always take the outline path below.
+    if stroke.is_none() {
+        if let Some(cached) = cache_color_glyph(
+            scale_factor,
+            atlas,
+            image,
+            tess_commands,
+            glyph,
+            stroke_join,
+            weight,
+            &variation_key,
+            border,
+            frame,
+            fill,
+            &face,
+        ) {
+            // A color glyph stores its RGBA as-is; the `true` flag tells the
+            // mesh builder to skip the per-segment fill tint.
+            return Some((cached.0, cached.1, true));
+        }
+    }
     let entry = GlyphEntry {
         font: glyph.font_id,
         glyph_id: glyph.glyph_id.into(),
@@ -424,10 +741,170 @@ pub(crate) fn cache_glyph(
         weight: weight.into(),
         stroke,
         join: stroke_join,
+        color: false,
+        variations: variation_key.clone(),
+        slant: FloatOrd(slant),
+        fill: None,
     };
     tess_commands.commands.clear();
-    face.outline_glyph(GlyphId(glyph.glyph_id), tess_commands)?;
+    // Shear in font units (x' = x + slant * y) so the lean is applied before the
+    // `font_size / unit_per_em` scale and stays consistent across sizes.
+    tess_commands.shear = applied_slant;
+    let outlined = face.outline_glyph(glyph_id, tess_commands);
+    tess_commands.shear = 0.0;
+    outlined?;
     let stroke = stroke.map(|x| x.get() as f32 * unit_per_em / 100.);
+    tess_commands
+        .tess_glyph(stroke, scale, atlas, image, entry, border, frame)
+        .map(|(rect, offset)| (rect, offset, false))
+}
+
+/// Rasterize a color glyph (COLR/CPAL layers or an embedded bitmap) into the
+/// color page of the atlas. Returns `None` when the face has no color data for
+/// this glyph, in which case the caller falls back to the outline path.
+fn cache_color_glyph(
+    scale_factor: f32,
+    atlas: &mut TextAtlas,
+    image: &mut Image,
+    tess_commands: &mut CommandEncoder,
+    glyph: &cosmic_text::LayoutGlyph,
+    stroke_join: StrokeJoin,
+    weight: Weight,
+    variation_key: &VariationKey,
+    border: GlyphBorder,
+    frame: u64,
+    fill: Vec4,
+    face: &Face,
+) -> Option<(Rect, Vec2)> {
+    let unit_per_em = face.units_per_em() as f32;
+    let glyph_id = GlyphId(glyph.glyph_id);
+    let entry = GlyphEntry {
+        font: glyph.font_id,
+        glyph_id: glyph.glyph_id.into(),
+        size: FloatOrd(glyph.font_size),
+        weight: weight.into(),
+        stroke: None,
+        join: stroke_join,
+        color: true,
+        variations: variation_key.clone(),
+        slant: FloatOrd(0.0),
+        // Foreground-referencing COLR layers bake the segment fill into the
+        // cached pixels, so the fill is part of the color key; keying every
+        // color entry on it keeps `get_atlas_rect` lookups consistent without
+        // needing the face to tell sentinel glyphs apart.
+        fill: Some(quantize_fill(fill)),
+    };
+
+    // Embedded bitmap strikes (CBDT/sbix). Decode at the closest available ppem
+    // and blit the premultiplied pixels straight into the atlas, scaling to the
+    // requested `font_size`.
+    let ppem = (glyph.font_size * scale_factor).round().max(1.) as u16;
+    if let Some(raster) = face.glyph_raster_image(glyph_id, ppem) {
+        return atlas.blit_raster_image(
+            image,
+            entry,
+            raster,
+            glyph.font_size * scale_factor,
+            border,
+            frame,
+        );
+    }
+
+    // Layered vector color (COLR v0) composited over the CPAL palette.
+    let layers = face.glyph_color_layers(glyph_id)?;
+    let cpal = face.tables().cpal?;
+    tess_commands.commands.clear();
     let scale = glyph.font_size / unit_per_em * scale_factor;
-    tess_commands.tess_glyph(stroke, scale, atlas, image, entry)
+    let mut emitted = false;
+    for layer in layers {
+        tess_commands.commands.clear();
+        if face.outline_glyph(layer.glyph_id, tess_commands).is_none() {
+            continue;
+        }
+        // `0xFFFF` is the COLR "use text foreground color" sentinel; every other
+        // index resolves against CPAL. Either way the result is converted into
+        // the atlas' linear, premultiplied working space before compositing.
+        let color = if layer.palette_index == 0xFFFF {
+            premultiply(fill)
+        } else {
+            cpal.get(0, layer.palette_index)
+                .map(srgb_to_atlas)
+                .unwrap_or(Vec4::ZERO)
+        };
+        tess_commands.tess_color_layer(scale, color, atlas, image, entry)?;
+        emitted = true;
+    }
+    // Nothing was actually drawn (empty layer list or every layer had no
+    // outline); fall back to the monochrome outline path rather than packing an
+    // empty rect as a valid color entry.
+    if !emitted {
+        return None;
+    }
+    atlas.finish_color_glyph(image, entry, border, frame)
+}
+
+/// Quantize a linear fill color to 8 bits per channel for use as part of a
+/// color [`GlyphEntry`] key, so segments that bake the same foreground color
+/// share a cached glyph while differing colors cache apart.
+fn quantize_fill(c: Vec4) -> [u8; 4] {
+    [
+        (c.x.clamp(0., 1.) * 255.).round() as u8,
+        (c.y.clamp(0., 1.) * 255.).round() as u8,
+        (c.z.clamp(0., 1.) * 255.).round() as u8,
+        (c.w.clamp(0., 1.) * 255.).round() as u8,
+    ]
+}
+
+/// Premultiply a straight-alpha linear color (the segment fill is already
+/// linear) so it composites the same way as resolved CPAL entries.
+fn premultiply(c: Vec4) -> Vec4 {
+    Vec4::new(c.x * c.w, c.y * c.w, c.z * c.w, c.w)
+}
+
+/// Convert an 8-bit sRGB CPAL entry into the atlas' linear, premultiplied
+/// working space so emoji colors don't shift against the linear atlas.
+fn srgb_to_atlas(c: cosmic_text::ttf_parser::RgbaColor) -> Vec4 {
+    fn to_linear(b: u8) -> f32 {
+        let s = b as f32 / 255.;
+        if s <= 0.04045 {
+            s / 12.92
+        } else {
+            ((s + 0.055) / 1.055).powf(2.4)
+        }
+    }
+    let a = c.alpha as f32 / 255.;
+    Vec4::new(
+        to_linear(c.red) * a,
+        to_linear(c.green) * a,
+        to_linear(c.blue) * a,
+        a,
+    )
+}
+
+/// Hashable form of a resolved variation-axis list, used as part of
+/// [`GlyphEntry`] so instances with different axis coordinates cache apart.
+pub(crate) type VariationKey = Box<[(u32, FloatOrd)]>;
+
+/// Merge the styling-wide axis settings with any segment-level overrides,
+/// returning the resolved `(Tag, value)` list to hand to `ttf_parser`.
+fn resolve_variations(styling: &Text3dStyling, attrs: &SegmentStyle) -> Vec<(Tag, f32)> {
+    let mut out = styling.variations.clone();
+    for &(tag, value) in &attrs.variations {
+        match out.iter_mut().find(|(t, _)| *t == tag) {
+            Some(slot) => slot.1 = value,
+            None => out.push((tag, value)),
+        }
+    }
+    out
+}
+
+/// Derive the cache key from resolved variations: sorted by tag so the key is
+/// order-independent, with each value wrapped in [`FloatOrd`] for hashing.
+fn variation_key(variations: &[(Tag, f32)]) -> VariationKey {
+    let mut key: Vec<_> = variations
+        .iter()
+        .map(|&(tag, value)| (tag.0, FloatOrd(value)))
+        .collect();
+    key.sort_unstable_by_key(|(tag, _)| *tag);
+    key.into_boxed_slice()
 }